@@ -0,0 +1,166 @@
+use url::Url;
+
+/// A credential to attach to requests sent to a particular host.
+#[derive(Debug, Clone)]
+pub enum AuthToken {
+    Bearer(String),
+    Basic { username: String, password: String },
+}
+
+impl AuthToken {
+    /// The value to send in the `Authorization` header for this credential.
+    pub fn header_value(&self) -> String {
+        match self {
+            AuthToken::Bearer(token) => format!("Bearer {}", token),
+            AuthToken::Basic { username, password } => {
+                format!("Basic {}", base64_encode(format!("{}:{}", username, password).as_bytes()))
+            }
+        }
+    }
+}
+
+/// Per-host credentials parsed from a `DOWNLOAD_AUTH_TOKENS`-style spec:
+/// a semicolon-separated list of `token@host` or `user:password@host`
+/// entries. Lookups match the exact host or any of its subdomains.
+#[derive(Debug, Clone, Default)]
+pub struct AuthTokens(Vec<(String, AuthToken)>);
+
+impl AuthTokens {
+    /// Parses a semicolon-separated `token@host` / `user:password@host` spec.
+    /// Malformed entries (missing `@`) are skipped.
+    pub fn parse(spec: &str) -> Self {
+        let mut tokens = Vec::new();
+        for entry in spec.split(';') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let Some((credential, host)) = entry.rsplit_once('@') else {
+                eprintln!("Ignoring malformed auth token entry (missing '@'): {}", entry);
+                continue;
+            };
+            let token = match credential.split_once(':') {
+                Some((username, password)) => AuthToken::Basic {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                },
+                None => AuthToken::Bearer(credential.to_string()),
+            };
+            tokens.push((host.to_lowercase(), token));
+        }
+        AuthTokens(tokens)
+    }
+
+    /// Adds or replaces the credential for `host`.
+    pub fn insert(&mut self, host: &str, token: AuthToken) {
+        let host = host.to_lowercase();
+        self.0.retain(|(h, _)| h != &host);
+        self.0.push((host, token));
+    }
+
+    /// Finds the credential configured for `host`, matching the host
+    /// itself or any of the hosts it's a subdomain of.
+    pub fn lookup(&self, host: &str) -> Option<&AuthToken> {
+        let host = host.to_lowercase();
+        self.0
+            .iter()
+            .find(|(configured, _)| {
+                host == *configured || host.ends_with(&format!(".{}", configured))
+            })
+            .map(|(_, token)| token)
+    }
+
+    /// Looks up the credential for `url`'s host, if any, and returns the
+    /// `Authorization` header value to send with the request.
+    pub fn header_for_url(&self, url: &str) -> Option<String> {
+        let host = Url::parse(url).ok()?.host_str()?.to_string();
+        self.lookup(&host).map(AuthToken::header_value)
+    }
+}
+
+/// Minimal base64 (standard alphabet, padded) encoder, used for `Basic` auth
+/// so we don't need to pull in a dedicated dependency for one call site.
+fn base64_encode(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = *chunk.get(1).unwrap_or(&0);
+        let b2 = *chunk.get(2).unwrap_or(&0);
+
+        out.push(ALPHABET[(b0 >> 2) as usize] as char);
+        out.push(ALPHABET[(((b0 & 0x03) << 4) | (b1 >> 4)) as usize] as char);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(((b1 & 0x0f) << 2) | (b2 >> 6)) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if chunk.len() > 2 {
+            ALPHABET[(b2 & 0x3f) as usize] as char
+        } else {
+            '='
+        });
+    }
+    out
+}
+
+/* TESTS */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bearer_entry() {
+        let tokens = AuthTokens::parse("secretkey@annas-archive.org");
+        match tokens.lookup("annas-archive.org") {
+            Some(AuthToken::Bearer(token)) => assert_eq!(token, "secretkey"),
+            other => panic!("expected Bearer token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_basic_entry() {
+        let tokens = AuthTokens::parse("alice:hunter2@mirror.example.com");
+        match tokens.lookup("mirror.example.com") {
+            Some(AuthToken::Basic { username, password }) => {
+                assert_eq!(username, "alice");
+                assert_eq!(password, "hunter2");
+            }
+            other => panic!("expected Basic token, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_subdomain_suffix_match() {
+        let tokens = AuthTokens::parse("secretkey@annas-archive.org");
+        assert!(tokens.lookup("cdn.annas-archive.org").is_some());
+        assert!(tokens.lookup("annas-archive.org.evil.com").is_none());
+    }
+
+    #[test]
+    fn test_lookup_miss_returns_none() {
+        let tokens = AuthTokens::parse("secretkey@annas-archive.org");
+        assert!(tokens.lookup("example.com").is_none());
+    }
+
+    #[test]
+    fn test_bearer_header_value() {
+        let token = AuthToken::Bearer("abc123".to_string());
+        assert_eq!(token.header_value(), "Bearer abc123");
+    }
+
+    #[test]
+    fn test_basic_header_value() {
+        let token = AuthToken::Basic {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+        };
+        assert_eq!(token.header_value(), "Basic YWxpY2U6aHVudGVyMg==");
+    }
+
+    #[test]
+    fn test_malformed_entry_is_skipped() {
+        let tokens = AuthTokens::parse("not-a-valid-entry");
+        assert!(tokens.lookup("not-a-valid-entry").is_none());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::config::CONFIG;
+use anyhow::{anyhow, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Cached metadata for a single `html_get_page` response, persisted next to
+/// its body under `CONFIG.cache_dir`.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub cache_control: String,
+    pub fetched_at: u64,
+}
+
+/// Returns the `(body_path, meta_path)` pair a URL is stored under, keyed by
+/// a hash of the URL so arbitrary characters never touch the filesystem.
+fn cache_paths(url: &str) -> (PathBuf, PathBuf) {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let key = format!("{:016x}", hasher.finish());
+    (
+        CONFIG.cache_dir.join(format!("{}.body", key)),
+        CONFIG.cache_dir.join(format!("{}.meta", key)),
+    )
+}
+
+/// Loads the cached body and metadata for `url`, if present on disk.
+pub fn load_entry(url: &str) -> Option<(String, CacheEntry)> {
+    let (body_path, meta_path) = cache_paths(url);
+    let body = fs::read_to_string(&body_path).ok()?;
+    let entry = read_meta(&meta_path)?;
+    Some((body, entry))
+}
+
+/// Writes `body` and its response metadata for `url` to the cache.
+pub fn store_entry(
+    url: &str,
+    body: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+    cache_control: &str,
+) -> Result<()> {
+    let (body_path, meta_path) = cache_paths(url);
+    fs::write(&body_path, body)?;
+    write_meta(
+        &meta_path,
+        &CacheEntry {
+            etag: etag.map(str::to_string),
+            last_modified: last_modified.map(str::to_string),
+            cache_control: cache_control.to_string(),
+            fetched_at: now(),
+        },
+    )
+}
+
+/// Bumps a cache entry's `fetched_at` timestamp after a `304 Not Modified`
+/// revalidation, without touching the body that's still known to be current.
+pub fn touch_entry(url: &str, entry: &CacheEntry) -> Result<()> {
+    let (_, meta_path) = cache_paths(url);
+    write_meta(
+        &meta_path,
+        &CacheEntry {
+            fetched_at: now(),
+            ..entry.clone()
+        },
+    )
+}
+
+/// `true` if `Cache-Control` forbids storing the response at all.
+pub fn is_no_store(cache_control: &str) -> bool {
+    cache_control
+        .split(',')
+        .any(|d| d.trim().eq_ignore_ascii_case("no-store"))
+}
+
+/// `true` if `Cache-Control` contains `no-cache`, which requires
+/// revalidation before reuse regardless of freshness. `max-age=0` is not
+/// detected here; `is_fresh` never treats such an entry as fresh anyway.
+fn is_no_cache(cache_control: &str) -> bool {
+    cache_control
+        .split(',')
+        .any(|d| d.trim().eq_ignore_ascii_case("no-cache"))
+}
+
+fn max_age(cache_control: &str) -> Option<u64> {
+    cache_control.split(',').find_map(|directive| {
+        let directive = directive.trim();
+        let value = directive.strip_prefix("max-age=")?;
+        value.parse::<u64>().ok()
+    })
+}
+
+/// Whether a cached entry can be served without a network round-trip.
+pub fn is_fresh(entry: &CacheEntry) -> bool {
+    if is_no_store(&entry.cache_control) || is_no_cache(&entry.cache_control) {
+        return false;
+    }
+    let Some(max_age) = max_age(&entry.cache_control) else {
+        return false;
+    };
+    now().saturating_sub(entry.fetched_at) < max_age
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn write_meta(path: &Path, entry: &CacheEntry) -> Result<()> {
+    let contents = format!(
+        "etag={}\nlast_modified={}\ncache_control={}\nfetched_at={}\n",
+        entry.etag.as_deref().unwrap_or(""),
+        entry.last_modified.as_deref().unwrap_or(""),
+        entry.cache_control,
+        entry.fetched_at,
+    );
+    fs::write(path, contents).map_err(|e| anyhow!("Failed to write cache metadata: {}", e))
+}
+
+fn read_meta(path: &Path) -> Option<CacheEntry> {
+    let contents = fs::read_to_string(path).ok()?;
+    let mut etag = None;
+    let mut last_modified = None;
+    let mut cache_control = String::new();
+    let mut fetched_at = 0u64;
+
+    for line in contents.lines() {
+        let (key, value) = line.split_once('=')?;
+        match key {
+            "etag" if !value.is_empty() => etag = Some(value.to_string()),
+            "last_modified" if !value.is_empty() => last_modified = Some(value.to_string()),
+            "cache_control" => cache_control = value.to_string(),
+            "fetched_at" => fetched_at = value.parse().ok()?,
+            _ => {}
+        }
+    }
+
+    Some(CacheEntry {
+        etag,
+        last_modified,
+        cache_control,
+        fetched_at,
+    })
+}
+
+/* TESTS */
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_age_parsing() {
+        assert_eq!(max_age("public, max-age=3600"), Some(3600));
+        assert_eq!(max_age("no-cache"), None);
+    }
+
+    #[test]
+    fn test_no_store_detection() {
+        assert!(is_no_store("no-store"));
+        assert!(!is_no_store("public, max-age=60"));
+    }
+
+    #[test]
+    fn test_freshness() {
+        let fresh = CacheEntry {
+            etag: None,
+            last_modified: None,
+            cache_control: "max-age=3600".to_string(),
+            fetched_at: now(),
+        };
+        assert!(is_fresh(&fresh));
+
+        let stale = CacheEntry {
+            fetched_at: now().saturating_sub(7200),
+            ..fresh.clone()
+        };
+        assert!(!is_fresh(&stale));
+
+        let uncacheable = CacheEntry {
+            cache_control: "no-cache".to_string(),
+            ..fresh
+        };
+        assert!(!is_fresh(&uncacheable));
+    }
+}
@@ -1,8 +1,10 @@
+use crate::auth::{AuthToken, AuthTokens};
 use dotenv::dotenv;
 use once_cell::sync::Lazy;
 use std::env;
 use std::path::PathBuf;
 use std::fs;
+use url::Url;
 
 /// List of supported book languages.
 static SUPPORTED_BOOK_LANGUAGE: Lazy<Vec<&'static str>> = Lazy::new(|| {
@@ -23,17 +25,24 @@ pub struct Config {
     pub log_dir: PathBuf,
     pub tmp_dir: PathBuf,
     pub ingest_dir: PathBuf,
+    pub cache_dir: PathBuf,
     pub status_timeout: u64,
 
     // Network settings
     pub max_retry: u32,
-    pub default_sleep: u64,
+    pub retry_wait_duration: u64,
+    pub max_retry_backoff: u64,
     pub cloudflare_proxy: String,
     pub use_cf_bypass: bool,
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub ca_cert_file: Option<PathBuf>,
+    pub max_redirects: u32,
 
     // Anna's Archive settings
     pub aa_donator_key: String,
     pub aa_base_url: String,
+    pub auth_tokens: AuthTokens,
 
     // File format settings
     pub supported_formats: Vec<String>,
@@ -61,6 +70,7 @@ impl Config {
 
         let tmp_dir = PathBuf::from(env::var("TMP_DIR").unwrap_or_else(|_| "/tmp/cwa-book-downloader".to_string()));
         let ingest_dir = PathBuf::from(env::var("INGEST_DIR").unwrap_or_else(|_| "/tmp/cwa-book-ingest".to_string()));
+        let cache_dir = PathBuf::from(env::var("CACHE_DIR").unwrap_or_else(|_| "/tmp/cwa-book-cache".to_string()));
         let status_timeout = env::var("STATUS_TIMEOUT")
             .unwrap_or_else(|_| "3600".to_string())
             .parse::<u64>()
@@ -70,16 +80,21 @@ impl Config {
         fs::create_dir_all(&tmp_dir).expect("Failed to create TMP_DIR");
         fs::create_dir_all(&log_dir).expect("Failed to create LOG_DIR");
         fs::create_dir_all(&ingest_dir).expect("Failed to create INGEST_DIR");
+        fs::create_dir_all(&cache_dir).expect("Failed to create CACHE_DIR");
 
         // Network settings
         let max_retry = env::var("MAX_RETRY")
             .unwrap_or_else(|_| "3".to_string())
             .parse::<u32>()
             .expect("MAX_RETRY must be a valid integer");
-        let default_sleep = env::var("DEFAULT_SLEEP")
+        let retry_wait_duration = env::var("RETRY_WAIT_DURATION")
             .unwrap_or_else(|_| "5".to_string())
             .parse::<u64>()
-            .expect("DEFAULT_SLEEP must be a valid integer");
+            .expect("RETRY_WAIT_DURATION must be a valid integer");
+        let max_retry_backoff = env::var("MAX_RETRY_BACKOFF")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .expect("MAX_RETRY_BACKOFF must be a valid integer");
         let cloudflare_proxy = env::var("CLOUDFLARE_PROXY_URL")
             .unwrap_or_else(|_| "http://localhost:8000".to_string());
         let use_cf_bypass = env::var("USE_CF_BYPASS")
@@ -87,6 +102,16 @@ impl Config {
             .to_lowercase()
             .parse::<bool>()
             .unwrap_or(true);
+        let http_proxy = env::var("HTTP_PROXY").ok().filter(|s| !s.trim().is_empty());
+        let https_proxy = env::var("HTTPS_PROXY").ok().filter(|s| !s.trim().is_empty());
+        let ca_cert_file = env::var("CA_CERT_FILE")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(PathBuf::from);
+        let max_redirects = env::var("MAX_REDIRECTS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<u32>()
+            .expect("MAX_REDIRECTS must be a valid integer");
 
         // Anna's Archive settings
         let aa_donator_key = env::var("AA_DONATOR_KEY").unwrap_or_else(|_| "".to_string()).trim().to_string();
@@ -95,6 +120,15 @@ impl Config {
             .trim_end_matches('/')
             .to_string();
 
+        let mut auth_tokens = env::var("DOWNLOAD_AUTH_TOKENS")
+            .map(|spec| AuthTokens::parse(&spec))
+            .unwrap_or_default();
+        if !aa_donator_key.is_empty() {
+            if let Some(aa_host) = Url::parse(&aa_base_url).ok().and_then(|u| u.host_str().map(str::to_string)) {
+                auth_tokens.insert(&aa_host, AuthToken::Bearer(aa_donator_key.clone()));
+            }
+        }
+
         // File format settings
         let supported_formats = env::var("SUPPORTED_FORMATS")
             .unwrap_or_else(|_| "epub,mobi,azw3,fb2,djvu,cbz,cbr".to_string())
@@ -139,13 +173,20 @@ impl Config {
             log_dir,
             tmp_dir,
             ingest_dir,
+            cache_dir,
             status_timeout,
             max_retry,
-            default_sleep,
+            retry_wait_duration,
+            max_retry_backoff,
             cloudflare_proxy,
             use_cf_bypass,
+            http_proxy,
+            https_proxy,
+            ca_cert_file,
+            max_redirects,
             aa_donator_key,
             aa_base_url,
+            auth_tokens,
             supported_formats,
             book_language,
             flask_host,
@@ -1,12 +1,22 @@
+mod auth;
+mod cache;
 mod config;
+mod network;
 
 use config::CONFIG;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Access configuration settings using the global CONFIG instance
-    let config = CONFIG.as_ref().expect("Failed to load configuration");
+    let config = &*CONFIG;
 
     println!("Base Directory: {:?}", config.base_dir);
 
+    // Warm the shared HTTP client and confirm Anna's Archive is reachable.
+    match network::html_get_page_cf(config.aa_base_url.clone(), true).await {
+        Ok(body) => println!("Fetched {} bytes from {}", body.len(), config.aa_base_url),
+        Err(e) => eprintln!("Failed to reach {}: {}", config.aa_base_url, e),
+    }
+
     Ok(())
 }
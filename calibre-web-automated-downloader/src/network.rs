@@ -1,10 +1,18 @@
+use crate::cache;
 use crate::config::CONFIG;
 use anyhow::{anyhow, Result};
 use axum::body::Bytes;
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
 use reqwest;
-use reqwest::Client;
-use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderValue, RANGE, USER_AGENT};
+use reqwest::{Client, Proxy, StatusCode};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio;
+use tokio::io::AsyncWriteExt;
 use url::Url;
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
@@ -15,116 +23,631 @@ static APP_USER_AGENT: &str = concat!(
     "Chrome/129.0.0.0 Safari/537.3"
 );
 
+/// A single, shared `reqwest::Client` reused by every fetch function so that
+/// connection pooling and TLS session resumption actually take effect.
+static CLIENT: Lazy<Client> = Lazy::new(build_client);
+
+/// Builds the shared HTTP client: rustls TLS, the app's `User-Agent` baked
+/// into the default headers, and optional proxy/CA configuration from `Config`.
+fn build_client() -> Client {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT));
+
+    let mut builder = Client::builder()
+        .use_rustls_tls()
+        .default_headers(headers)
+        // Redirects are followed manually by callers (see `html_get_page`
+        // and `send_following_redirects`) using `resolve_redirect_url`.
+        .redirect(reqwest::redirect::Policy::none());
+
+    if let Some(proxy_url) = CONFIG.https_proxy.as_ref().or(CONFIG.http_proxy.as_ref()) {
+        match Proxy::all(proxy_url) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => eprintln!("Invalid proxy URL '{}': {}", proxy_url, e),
+        }
+    }
+
+    if let Some(ca_cert_file) = &CONFIG.ca_cert_file {
+        match fs::read(ca_cert_file) {
+            Ok(pem) => match reqwest::Certificate::from_pem(&pem) {
+                Ok(cert) => builder = builder.add_root_certificate(cert),
+                Err(e) => eprintln!("Failed to parse CA_CERT_FILE '{:?}': {}", ca_cert_file, e),
+            },
+            Err(e) => eprintln!("Failed to read CA_CERT_FILE '{:?}': {}", ca_cert_file, e),
+        }
+    }
+
+    builder.build().expect("Failed to build shared HTTP client")
+}
+
+/// Resolves a `Location` header against the URL it was received on, per
+/// RFC 3986: absolute `http(s)://` URLs pass through untouched,
+/// protocol-relative `//authority` locations inherit the current scheme,
+/// path-absolute `/path` locations inherit the current scheme and
+/// authority, and anything else is joined as a relative reference.
+fn resolve_redirect_url(base: &str, location: &str) -> Result<String> {
+    let location = location.trim();
+
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return Ok(location.to_string());
+    }
+
+    let base_parsed =
+        Url::parse(base).map_err(|e| anyhow!("Failed to parse base URL '{}': {}", base, e))?;
+
+    if let Some(authority) = location.strip_prefix("//") {
+        return Ok(format!("{}://{}", base_parsed.scheme(), authority));
+    }
+
+    if location.starts_with('/') {
+        let mut absolute = format!(
+            "{}://{}",
+            base_parsed.scheme(),
+            base_parsed.host_str().unwrap_or_default()
+        );
+        if let Some(port) = base_parsed.port() {
+            absolute.push_str(&format!(":{}", port));
+        }
+        absolute.push_str(location);
+        return Ok(absolute);
+    }
+
+    base_parsed
+        .join(location)
+        .map(|u| u.to_string())
+        .map_err(|e| anyhow!("Failed to resolve redirect location '{}': {}", location, e))
+}
+
+/// Decompresses a response body according to its `Content-Encoding`,
+/// falling back to treating it as identity-encoded if the header is absent
+/// or unrecognized. A body that does not decode under its declared `gzip`
+/// or `br` encoding is an error.
+fn decompress_body(bytes: &[u8], content_encoding: Option<&str>) -> Result<Vec<u8>> {
+    match content_encoding.map(|e| e.to_lowercase()) {
+        Some(encoding) if encoding == "gzip" => {
+            let mut decoder = flate2::read::GzDecoder::new(bytes);
+            let mut decoded = Vec::new();
+            decoder
+                .read_to_end(&mut decoded)
+                .map_err(|e| anyhow!("Failed to decode gzip response body: {}", e))?;
+            Ok(decoded)
+        }
+        Some(encoding) if encoding == "br" => {
+            let mut decoded = Vec::new();
+            brotli::BrotliDecompress(&mut std::io::Cursor::new(bytes), &mut decoded)
+                .map_err(|e| anyhow!("Failed to decode brotli response body: {}", e))?;
+            Ok(decoded)
+        }
+        _ => Ok(bytes.to_vec()),
+    }
+}
+
+/// Extracts the `charset` parameter from a `Content-Type` header value,
+/// e.g. `"text/html; charset=ISO-8859-1"` -> `Some("ISO-8859-1")`.
+fn charset_from_content_type(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let param = param.trim();
+        param.strip_prefix("charset=").map(|v| v.trim_matches('"'))
+    })
+}
+
+/// Decompresses a response body per `Content-Encoding`, then transcodes it
+/// to UTF-8 per the charset declared in `Content-Type` (defaulting to
+/// UTF-8, matching `reqwest::Response::text()`'s behavior), rather than
+/// assuming the body is already UTF-8.
+fn decode_body(bytes: &[u8], content_encoding: Option<&str>, content_type: Option<&str>) -> Result<String> {
+    let decompressed = decompress_body(bytes, content_encoding)?;
+
+    let encoding = content_type
+        .and_then(charset_from_content_type)
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(&decompressed);
+    Ok(text.into_owned())
+}
+
+/// Computes the exponential backoff delay for a given retry `attempt`
+/// (0-indexed): `retry_wait_duration * 2^attempt`, capped at
+/// `max_retry_backoff`, with up to 25% random jitter added so concurrent
+/// downloads hitting the same mirror don't all retry in lockstep.
+fn backoff_delay(attempt: u32) -> Duration {
+    let base = CONFIG
+        .retry_wait_duration
+        .saturating_mul(1u64 << attempt.min(20));
+    let capped_secs = base.min(CONFIG.max_retry_backoff);
+    let jitter_ms = (jitter_fraction() * capped_secs as f64 * 250.0) as u64;
+    Duration::from_millis(capped_secs * 1000 + jitter_ms)
+}
+
+/// A pseudo-random value in `[0, 1)`, good enough for retry jitter.
+fn jitter_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    (nanos % 1_000) as f64 / 1_000.0
+}
+
+/// Parses a `Retry-After` header value, which is either a number of
+/// delta-seconds or an HTTP-date to wait until.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let target = parse_http_date(value)?;
+    target.duration_since(SystemTime::now()).ok()
+}
+
+/// Parses an RFC 1123 HTTP-date (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`),
+/// the only form modern servers send.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 6 {
+        return None;
+    }
+    let day: i64 = parts[1].parse().ok()?;
+    let month = match parts[2] {
+        "Jan" => 1,
+        "Feb" => 2,
+        "Mar" => 3,
+        "Apr" => 4,
+        "May" => 5,
+        "Jun" => 6,
+        "Jul" => 7,
+        "Aug" => 8,
+        "Sep" => 9,
+        "Oct" => 10,
+        "Nov" => 11,
+        "Dec" => 12,
+        _ => return None,
+    };
+    let year: i64 = parts[3].parse().ok()?;
+    let mut time = parts[4].split(':');
+    let hour: i64 = time.next()?.parse().ok()?;
+    let min: i64 = time.next()?.parse().ok()?;
+    let sec: i64 = time.next()?.parse().ok()?;
+
+    let days = days_from_civil(year, month, day);
+    let total_secs = days * 86_400 + hour * 3_600 + min * 60 + sec;
+    if total_secs < 0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(total_secs as u64))
+}
+
+/// Parses the total resource length out of a `Content-Range` header value
+/// sent with a `416 Range Not Satisfiable` response, e.g.
+/// `"bytes */12345"` -> `Some(12345)`. Returns `None` if the total is
+/// unknown (`"bytes */*"`) or the header is malformed.
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.rsplit('/').next()?.trim().parse().ok()
+}
+
+/// Days since the Unix epoch for a given civil (Gregorian) date, using
+/// Howard Hinnant's `days_from_civil` algorithm.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
 /// Fetches HTML from a given URL, retrying on error up to `CONFIG.max_retry` times.
 ///
+/// If `bypass_cache` is `false`, a fresh on-disk cache entry (see the `cache`
+/// module) is returned without a network call, and a stale entry is
+/// revalidated with `If-None-Match`/`If-Modified-Since` instead of being
+/// re-fetched wholesale.
+///
+/// The shared client never follows redirects itself (see `build_client`);
+/// instead, 3xx responses are resolved and re-issued here, up to
+/// `CONFIG.max_redirects` hops. Each hop's `Authorization` header is
+/// recomputed from scratch via `CONFIG.auth_tokens.header_for_url`, which is
+/// host-scoped, so a redirect to a different host naturally picks up that
+/// host's own configured credential (if any) instead of leaking the
+/// original host's. Responses are requested with `Accept-Encoding: gzip, br`
+/// and decoded accordingly (see `decode_body`).
+///
 /// Returns the response body if successful, or an `anyhow::Error` if:
 /// - The request fails to send,
 /// - The server returns an unsuccessful status,
 /// - The response body cannot be read,
+/// - too many redirects are followed,
 /// - or all retries are exhausted.
-pub async fn html_get_page(url: String) -> Result<String> {
-    let client = Client::new();
-    println!("GET {}", url);
-
-    for attempt in 0..CONFIG.max_retry {
-        println!("Attempt {}", attempt + 1);
-
-        // Try sending the request
-        let response = match client
-            .get(&url)
-            .header("User-Agent", APP_USER_AGENT)
-            .send()
-            .await
-        {
-            Ok(resp) => resp,
-            Err(e) => {
-                // Sending the request failed (network error, DNS error, etc.)
+pub async fn html_get_page(url: String, bypass_cache: bool) -> Result<String> {
+    let client = &*CLIENT;
+    let mut current_url = url;
+
+    for redirect in 0..=CONFIG.max_redirects {
+        println!("GET {}", current_url);
+
+        let cached = if bypass_cache {
+            None
+        } else {
+            cache::load_entry(&current_url)
+        };
+        if let Some((body, entry)) = &cached {
+            if cache::is_fresh(entry) {
+                println!("Cache hit (fresh) for {}", current_url);
+                return Ok(body.clone());
+            }
+        }
+
+        let mut redirected_to = None;
+
+        for attempt in 0..CONFIG.max_retry {
+            println!("Attempt {}", attempt + 1);
+
+            let mut request = client.get(&current_url).header("Accept-Encoding", "gzip, br");
+            if let Some((_, entry)) = &cached {
+                if let Some(etag) = &entry.etag {
+                    request = request.header("If-None-Match", etag);
+                }
+                if let Some(last_modified) = &entry.last_modified {
+                    request = request.header("If-Modified-Since", last_modified);
+                }
+            }
+
+            if let Some(auth) = CONFIG.auth_tokens.header_for_url(&current_url) {
+                request = request.header(reqwest::header::AUTHORIZATION, auth);
+            }
+
+            // Try sending the request
+            let response = match request.send().await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    // Sending the request failed (network error, DNS error, etc.)
+                    if attempt + 1 >= CONFIG.max_retry {
+                        return Err(anyhow!(
+                            "Network error after {} attempts: {}",
+                            attempt + 1,
+                            e
+                        ));
+                    } else {
+                        let delay = backoff_delay(attempt);
+                        println!("Network error: {}. Retrying in {:?}...", e, delay);
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
+                }
+            };
+
+            // A 304 means our cached copy is still good; refresh its freshness
+            // timestamp and hand it back without touching the network again.
+            if response.status() == StatusCode::NOT_MODIFIED {
+                if let Some((body, entry)) = &cached {
+                    println!("Cache revalidated (304) for {}", current_url);
+                    let _ = cache::touch_entry(&current_url, entry);
+                    return Ok(body.clone());
+                }
+            }
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or_else(|| {
+                        anyhow!("Redirect response from {} had no Location header", current_url)
+                    })?
+                    .to_string();
+                let resolved = resolve_redirect_url(&current_url, &location)?;
+                println!("Redirecting to {}", resolved);
+                redirected_to = Some(resolved);
+                break;
+            }
+
+            // Check if status code is 2xx
+            if !response.status().is_success() {
+                let status = response.status();
+
+                // Permanent client errors (other than rate-limiting) won't
+                // succeed on retry, so fail fast instead of burning attempts.
+                if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                    return Err(anyhow!(
+                        "Server returned non-success status {} (not retrying)",
+                        status
+                    ));
+                }
+
                 if attempt + 1 >= CONFIG.max_retry {
                     return Err(anyhow!(
-                        "Network error after {} attempts: {}",
-                        attempt + 1,
-                        e
+                        "Server returned non-success status {} after {} attempts",
+                        status,
+                        attempt + 1
                     ));
-                } else {
-                    let delay = Duration::from_secs(CONFIG.retry_wait_duration);
-                    println!(
-                        "Network error: {}. Retrying in {}s...",
-                        e, CONFIG.retry_wait_duration
-                    );
-                    tokio::time::sleep(delay).await;
-                    continue;
                 }
-            }
-        };
 
-        // Check if status code is 2xx
-        if !response.status().is_success() {
-            if attempt + 1 >= CONFIG.max_retry {
-                return Err(anyhow!(
-                    "Server returned non-success status {} after {} attempts",
-                    response.status(),
-                    attempt + 1
-                ));
-            } else {
-                let delay = Duration::from_secs(CONFIG.retry_wait_duration);
-                println!(
-                    "HTTP status error: {}. Retrying in {}s...",
-                    response.status(),
-                    CONFIG.retry_wait_duration
-                );
+                let delay = if status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::SERVICE_UNAVAILABLE
+                {
+                    response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(parse_retry_after)
+                        .unwrap_or_else(|| backoff_delay(attempt))
+                } else {
+                    backoff_delay(attempt)
+                };
+
+                println!("HTTP status error: {}. Retrying in {:?}...", status, delay);
                 tokio::time::sleep(delay).await;
                 continue;
             }
-        }
 
-        // We have a 2xx status, so let's read the body
-        match response.text().await {
-            Ok(body) => {
-                println!("Success!");
-                return Ok(body);
+            let etag = response
+                .headers()
+                .get("ETag")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let last_modified = response
+                .headers()
+                .get("Last-Modified")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let cache_control = response
+                .headers()
+                .get("Cache-Control")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+                .to_string();
+            let content_encoding = response
+                .headers()
+                .get("Content-Encoding")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+
+            // We have a 2xx status, so let's read the (possibly compressed) body
+            match response.bytes().await {
+                Ok(raw) => {
+                    let body =
+                        decode_body(&raw, content_encoding.as_deref(), content_type.as_deref())?;
+                    println!("Success!");
+                    if !bypass_cache && !cache::is_no_store(&cache_control) {
+                        if let Err(e) = cache::store_entry(
+                            &current_url,
+                            &body,
+                            etag.as_deref(),
+                            last_modified.as_deref(),
+                            &cache_control,
+                        ) {
+                            eprintln!("Failed to write cache entry for {}: {}", current_url, e);
+                        }
+                    }
+                    return Ok(body);
+                }
+                Err(e) => {
+                    if attempt + 1 >= CONFIG.max_retry {
+                        return Err(anyhow!(
+                            "Failed to read response body after {} attempts: {}",
+                            attempt + 1,
+                            e
+                        ));
+                    }
+                    let delay = backoff_delay(attempt);
+                    println!("Error reading body: {}. Retrying in {:?}...", e, delay);
+                    tokio::time::sleep(delay).await;
+                }
             }
-            Err(e) => {
-                if attempt + 1 >= CONFIG.max_retry {
+        }
+
+        match redirected_to {
+            Some(next_url) => {
+                if redirect >= CONFIG.max_redirects {
                     return Err(anyhow!(
-                        "Failed to read response body after {} attempts: {}",
-                        attempt + 1,
-                        e
+                        "Exceeded max_redirects ({}) while fetching {}",
+                        CONFIG.max_redirects,
+                        next_url
                     ));
                 }
-                let delay = Duration::from_secs(CONFIG.retry_wait_duration);
-                println!(
-                    "Error reading body: {}. Retrying in {}s...",
-                    e, CONFIG.retry_wait_duration
-                );
-                tokio::time::sleep(delay).await;
+                current_url = next_url;
+                continue;
+            }
+            None => {
+                // The retry loop above exhausted its attempts without
+                // returning; surface that as exhausted retries.
+                return Err(anyhow!(
+                    "Exhausted all retries ({} attempts) for URL: {}",
+                    CONFIG.max_retry,
+                    current_url
+                ));
             }
         }
     }
 
-    // If we exit the loop, we've exhausted all retries
     Err(anyhow!(
-        "Exhausted all retries ({} attempts) for URL: {}",
-        CONFIG.max_retry,
+        "Exceeded max_redirects ({}) while fetching {}",
+        CONFIG.max_redirects,
+        current_url
+    ))
+}
+
+/// Sends a request built by `build_request` for each hop, following 3xx
+/// responses up to `CONFIG.max_redirects` times. Used by the plain download
+/// functions, since the shared `CLIENT` is built with
+/// `redirect::Policy::none()` (see `build_client`) so that `html_get_page`
+/// can handle redirects itself; these callers need the same handling so a
+/// redirecting mirror link doesn't come back as a 3xx response body.
+async fn send_following_redirects(
+    url: &str,
+    build_request: impl Fn(&str) -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let mut current_url = url.to_string();
+
+    for _ in 0..=CONFIG.max_redirects {
+        let response = build_request(&current_url).send().await?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| {
+                    anyhow!("Redirect response from {} had no Location header", current_url)
+                })?
+                .to_string();
+            current_url = resolve_redirect_url(&current_url, &location)?;
+            continue;
+        }
+
+        return Ok(response);
+    }
+
+    Err(anyhow!(
+        "Exceeded max_redirects ({}) while fetching {}",
+        CONFIG.max_redirects,
         url
     ))
 }
 
-pub async fn html_get_page_cf(url: String) -> Result<String> {
+pub async fn html_get_page_cf(url: String, bypass_cache: bool) -> Result<String> {
     if CONFIG.use_cf_bypass {
-        return html_get_page(url).await;
+        return html_get_page(url, bypass_cache).await;
     } else {
         let cf_url = format!("{}/html?url={}", CONFIG.cloudflare_proxy, url);
-        return html_get_page(cf_url).await;
+        return html_get_page(cf_url, bypass_cache).await;
     }
 }
 
 pub async fn download_url(url: &str) -> Result<Bytes> {
-    let response = Client::new().get(url).send().await?; // Send the HTTP GET request
+    let response = send_following_redirects(url, |current_url| {
+        let mut request = CLIENT.get(current_url);
+        if let Some(auth) = CONFIG.auth_tokens.header_for_url(current_url) {
+            request = request.header(reqwest::header::AUTHORIZATION, auth);
+        }
+        request
+    })
+    .await?;
 
     // Directly return the body as a Bytes object
     Ok(response.bytes().await?)
 }
 
+/// Streams `url` to disk instead of buffering it, so large archives don't
+/// blow up memory and interrupted downloads can resume.
+///
+/// `dest_name` is the file's name relative to both `CONFIG.tmp_dir` (where
+/// the in-progress download lives) and `CONFIG.ingest_dir` (where the
+/// finished file is atomically renamed to); it must be a bare file name
+/// (no path separators), since it ultimately comes from scraped, untrusted
+/// book metadata and must not be able to escape either directory. If a
+/// partial download from a previous attempt is found under `tmp_dir`, a
+/// `Range` request resumes it; if the server ignores the range and answers
+/// `200 OK` instead of `206 Partial Content`, the partial file is truncated
+/// and restarted. If the server answers `416 Range Not Satisfiable`, the
+/// `Content-Range: bytes */<total>` it sends back is checked against the
+/// partial file's size: if they match, a prior run likely finished writing
+/// it but crashed before the final rename, so it's renamed into place as
+/// complete; otherwise the partial file is stale or corrupt, so it's
+/// discarded and the download restarts from scratch.
+/// `progress` is called after every chunk with `(bytes_downloaded, total)`,
+/// where `total` is `None` if the server didn't send a `Content-Length`.
+pub async fn download_to_file(
+    url: &str,
+    dest_name: &str,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<PathBuf> {
+    if Path::new(dest_name).file_name().and_then(|n| n.to_str()) != Some(dest_name) {
+        return Err(anyhow!(
+            "Invalid destination file name (must not contain path separators): {:?}",
+            dest_name
+        ));
+    }
+
+    let tmp_path = CONFIG.tmp_dir.join(dest_name);
+    let final_path = CONFIG.ingest_dir.join(dest_name);
+
+    let mut resume_from = fs::metadata(&tmp_path).map(|m| m.len()).unwrap_or(0);
+
+    let response = loop {
+        let response = send_following_redirects(url, |current_url| {
+            let mut request = CLIENT.get(current_url);
+            if let Some(auth) = CONFIG.auth_tokens.header_for_url(current_url) {
+                request = request.header(reqwest::header::AUTHORIZATION, auth);
+            }
+            if resume_from > 0 {
+                request = request.header(RANGE, format!("bytes={}-", resume_from));
+            }
+            request
+        })
+        .await?;
+
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+            let total = response
+                .headers()
+                .get(reqwest::header::CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_content_range_total);
+
+            if total == Some(resume_from) {
+                println!("{} already fully downloaded; finishing rename", url);
+                tokio::fs::rename(&tmp_path, &final_path).await?;
+                return Ok(final_path);
+            }
+
+            // The partial file doesn't match what the server has (stale or
+            // corrupt leftover from a crashed run, or the remote resource
+            // changed size under the same URL); throw it away and restart.
+            println!(
+                "Partial download for {} doesn't match remote size; restarting",
+                url
+            );
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            resume_from = 0;
+            continue;
+        }
+
+        break response;
+    };
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Server returned non-success status {} for {}",
+            response.status(),
+            url
+        ));
+    }
+
+    // The server may ignore our Range header and send the whole file back;
+    // in that case we must throw away whatever partial data we had.
+    let resuming = response.status() == StatusCode::PARTIAL_CONTENT;
+    let total = response
+        .content_length()
+        .map(|len| if resuming { len + resume_from } else { len });
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resuming)
+        .truncate(!resuming)
+        .open(&tmp_path)
+        .await?;
+
+    let mut downloaded = if resuming { resume_from } else { 0 };
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        progress(downloaded, total);
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp_path, &final_path).await?;
+    Ok(final_path)
+}
+
 fn get_absolute_url(base_url: &str, url: &str) -> Result<String> {
     // If the URL is empty, return an empty string
     if url.trim().is_empty() {
@@ -178,7 +701,7 @@ mod tests {
         let url = format!("{}/success", &mock_server.uri());
 
         // Call the `html_get_page` function
-        let result = html_get_page(url).await;
+        let result = html_get_page(url, false).await;
 
         // Assert that the result matches the expected body
         assert_eq!(result.unwrap(), expected_body);
@@ -202,7 +725,7 @@ mod tests {
         let url = format!("{}/always_fail", &mock_server.uri());
 
         // Call the `html_get_page` function
-        let result = html_get_page(url).await;
+        let result = html_get_page(url, false).await;
 
         // Assert that the result is an error
         assert!(
@@ -217,7 +740,7 @@ mod tests {
         let invalid_url = "http://".to_string(); // <-- deliberately broken
 
         // Call the `html_get_page` function
-        let result = html_get_page(invalid_url).await;
+        let result = html_get_page(invalid_url, false).await;
 
         // Assert that the result is an error
         assert!(result.is_err(), "Expected an error for invalid URL");
@@ -288,4 +811,145 @@ mod tests {
         let result = get_absolute_url(base_url, relative_url);
         assert!(result.is_err());
     }
+
+    #[test]
+    async fn test_resolve_redirect_absolute() {
+        let base = "https://mirror.example.com/page";
+        let location = "https://another.example.com/next";
+        let result = resolve_redirect_url(base, location).unwrap();
+        assert_eq!(result, "https://another.example.com/next");
+    }
+
+    #[test]
+    async fn test_resolve_redirect_protocol_relative() {
+        let base = "https://mirror.example.com/page";
+        let location = "//cdn.example.com/asset";
+        let result = resolve_redirect_url(base, location).unwrap();
+        assert_eq!(result, "https://cdn.example.com/asset");
+    }
+
+    #[test]
+    async fn test_resolve_redirect_path_absolute() {
+        let base = "https://mirror.example.com:8443/old/page";
+        let location = "/new/page";
+        let result = resolve_redirect_url(base, location).unwrap();
+        assert_eq!(result, "https://mirror.example.com:8443/new/page");
+    }
+
+    #[test]
+    async fn test_resolve_redirect_relative() {
+        let base = "https://mirror.example.com/books/123/";
+        let location = "download";
+        let result = resolve_redirect_url(base, location).unwrap();
+        assert_eq!(result, "https://mirror.example.com/books/123/download");
+    }
+
+    #[tokio::test]
+    async fn test_download_to_file_streams_and_renames() {
+        let mock_server = MockServer::start().await;
+        let expected_body = b"streamed book contents";
+
+        Mock::given(method("GET"))
+            .and(path("/book.epub"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(expected_body.to_vec()))
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}/book.epub", &mock_server.uri());
+        let mut last_progress = (0u64, None);
+
+        let final_path = download_to_file(&url, "test-download.epub", |downloaded, total| {
+            last_progress = (downloaded, total);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(final_path, CONFIG.ingest_dir.join("test-download.epub"));
+        assert_eq!(fs::read(&final_path).unwrap(), expected_body);
+        assert_eq!(last_progress, (expected_body.len() as u64, Some(expected_body.len() as u64)));
+
+        let _ = fs::remove_file(&final_path);
+    }
+
+    #[tokio::test]
+    async fn test_download_to_file_rejects_path_traversal_dest_name() {
+        let result = download_to_file("https://example.com/book.epub", "../escape.epub", |_, _| {}).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn test_parse_content_range_total() {
+        assert_eq!(parse_content_range_total("bytes */12345"), Some(12345));
+        assert_eq!(parse_content_range_total("bytes 0-99/500"), Some(500));
+        assert_eq!(parse_content_range_total("bytes */*"), None);
+    }
+
+    #[test]
+    async fn test_decode_body_identity() {
+        let body = decode_body(b"plain text", None, None).unwrap();
+        assert_eq!(body, "plain text");
+    }
+
+    #[test]
+    async fn test_decode_body_gzip() {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"gzipped text").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let body = decode_body(&compressed, Some("gzip"), None).unwrap();
+        assert_eq!(body, "gzipped text");
+    }
+
+    #[test]
+    async fn test_decode_body_brotli() {
+        let mut compressed = Vec::new();
+        brotli::BrotliCompress(
+            &mut std::io::Cursor::new(b"brotli text"),
+            &mut compressed,
+            &brotli::enc::BrotliEncoderParams::default(),
+        )
+        .unwrap();
+
+        let body = decode_body(&compressed, Some("br"), None).unwrap();
+        assert_eq!(body, "brotli text");
+    }
+
+    #[test]
+    async fn test_decode_body_respects_charset() {
+        // "café" in ISO-8859-1 (Latin-1): "caf" + 0xE9.
+        let latin1 = b"caf\xe9".to_vec();
+        let body = decode_body(&latin1, None, Some("text/html; charset=ISO-8859-1")).unwrap();
+        assert_eq!(body, "café");
+    }
+
+    #[test]
+    async fn test_parse_retry_after_delta_seconds() {
+        let delay = parse_retry_after("120").unwrap();
+        assert_eq!(delay, Duration::from_secs(120));
+    }
+
+    #[test]
+    async fn test_parse_retry_after_http_date_in_past_is_none() {
+        // A date far in the past should yield a negative duration,
+        // which `duration_since` reports as an error.
+        assert!(parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT").is_none());
+    }
+
+    #[test]
+    async fn test_parse_http_date_known_instant() {
+        // 2000-01-01T00:00:00Z is 946684800 seconds after the Unix epoch.
+        let parsed = parse_http_date("Sat, 01 Jan 2000 00:00:00 GMT").unwrap();
+        assert_eq!(
+            parsed.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::from_secs(946_684_800)
+        );
+    }
+
+    #[test]
+    async fn test_backoff_delay_is_capped() {
+        let delay = backoff_delay(30);
+        let max_expected = Duration::from_millis(CONFIG.max_retry_backoff * 1250);
+        assert!(delay <= max_expected, "{:?} should be <= {:?}", delay, max_expected);
+    }
 }